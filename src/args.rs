@@ -0,0 +1,75 @@
+use clap::{Parser, Subcommand};
+
+/// Command line arguments for cgip.
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None)]
+pub struct Args {
+    /// The question or prompt to send to the model
+    pub query: Option<String>,
+
+    /// Read the prompt from a file instead of (or in addition to) the command line
+    #[arg(short, long)]
+    pub file: Option<String>,
+
+    /// Apply a predefined role from roles.yaml before sending the prompt
+    #[arg(long)]
+    pub role: Option<String>,
+
+    /// Print the outgoing request and exit without calling the API
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(subcommand)]
+    pub subcmd: Option<SubCommands>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SubCommands {
+    /// Get or set a configuration value
+    Config(ConfigSubCommand),
+    /// View the current conversation context
+    View(ViewSubCommand),
+    /// Manage saved sessions
+    Session(SessionSubCommand),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ConfigSubCommand {
+    /// The configuration key to read or write
+    pub key: String,
+
+    /// The value to set; omit to print the current value
+    pub value: Option<String>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct ViewSubCommand {
+    /// Exclude the system prompt from the output
+    #[arg(long)]
+    pub exclude_system: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct SessionSubCommand {
+    /// Name of the session to operate on
+    pub name: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_role_flag_parses_and_is_reachable() {
+        let args = Args::try_parse_from(["cgip", "--role", "reviewer", "hello"]).unwrap();
+        assert_eq!(args.role.as_deref(), Some("reviewer"));
+        assert_eq!(args.query.as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn test_dry_run_flag_parses_and_is_reachable() {
+        let args = Args::try_parse_from(["cgip", "--dry-run", "hello"]).unwrap();
+        assert!(args.dry_run);
+        assert_eq!(args.query.as_deref(), Some("hello"));
+    }
+}
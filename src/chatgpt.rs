@@ -1,4 +1,4 @@
-use config::{Config, File as ConfigFile, FileFormat};
+use config::{Config, Environment, File as ConfigFile, FileFormat};
 use dirs::config_dir;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
@@ -16,6 +16,15 @@ pub struct AppConfig {
     pub show_progress: bool,
     pub show_context: bool,
     pub markdown: bool,
+    pub stream: bool,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub api_base: String,
+    pub api_key: Option<String>,
+    pub max_context_tokens: Option<u64>,
+    pub proxy: Option<String>,
+    pub dry_run: bool,
 }
 
 impl Default for AppConfig {
@@ -25,6 +34,15 @@ impl Default for AppConfig {
             show_progress: false,
             show_context: false,
             markdown: false,
+            stream: false,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            api_base: "https://api.openai.com/v1".to_string(),
+            api_key: None,
+            max_context_tokens: None,
+            proxy: None,
+            dry_run: false,
         }
     }
 }
@@ -33,6 +51,14 @@ impl Default for AppConfig {
 struct ChatRequest {
     pub model: String,
     pub messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -81,6 +107,21 @@ struct Choice {
     index: u64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct Delta {
+    content: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamChoice {
+    delta: Delta,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChatStreamChunk {
+    choices: Vec<StreamChoice>,
+}
+
 fn parse_response(json_str: &str) -> Result<ChatResponse> {
     serde_json::from_str(json_str)
 }
@@ -89,10 +130,72 @@ fn parse_error_response(json_str: &str) -> Result<ErrorResponse> {
     serde_json::from_str(json_str)
 }
 
+fn parse_stream_chunk(json_str: &str) -> Result<ChatStreamChunk> {
+    serde_json::from_str(json_str)
+}
+
+// Reads a `text/event-stream` response line by line, printing each delta to
+// stdout as it arrives and returning the fully accumulated text.
+fn read_streaming_response(response: reqwest::blocking::Response) -> String {
+    use std::io::BufRead;
+
+    // A non-2xx response (bad API key, rate limit, ...) is a plain JSON error
+    // object, not SSE. Surface it the same way the non-streaming path does
+    // instead of silently reading it line-by-line as if it were a stream.
+    if !response.status().is_success() {
+        let body = response.text().unwrap_or_default();
+        match parse_error_response(&body) {
+            Ok(error_response) => panic!("Error in response: {}", error_response.error.message),
+            Err(_) => panic!("Error in response: {}", body),
+        }
+    }
+
+    let reader = std::io::BufReader::new(response);
+    let mut full_text = String::new();
+
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => panic!("Error while reading stream: {}", e),
+        };
+
+        let Some(data) = line.strip_prefix("data: ") else {
+            continue;
+        };
+
+        if data == "[DONE]" {
+            break;
+        }
+
+        let chunk = match parse_stream_chunk(data) {
+            Ok(chunk) => chunk,
+            Err(e) => panic!("Error while parsing stream chunk: {}", e),
+        };
+
+        // The final usage-only chunk some OpenAI-compatible servers send has
+        // an empty `choices` array.
+        if let Some(content) = chunk.choices.first().and_then(|c| c.delta.content.clone()) {
+            print!("{}", content);
+            let _ = std::io::Write::flush(&mut std::io::stdout());
+            full_text.push_str(&content);
+        }
+    }
+
+    println!();
+    full_text
+}
+
 pub struct GptClient {
     pub config: AppConfig,
     pub config_directory: PathBuf,
     pub messages: Vec<Message>,
+    pub roles: Vec<RoleDefinition>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RoleDefinition {
+    pub name: String,
+    pub prompt: String,
 }
 
 pub enum Role {
@@ -137,6 +240,54 @@ fn ensure_config_file(dir: &PathBuf) -> std::io::Result<PathBuf> {
     Ok(config_path)
 }
 
+// Reads the `roles.yaml` file from the config directory, if present. Missing
+// file means no roles are configured, which is not an error.
+fn load_roles(dir: &PathBuf) -> Vec<RoleDefinition> {
+    let roles_path = dir.join("roles.yaml");
+    if !roles_path.exists() {
+        return Vec::new();
+    }
+
+    let contents =
+        fs::read_to_string(&roles_path).expect("Failed to read roles file");
+    serde_yaml::from_str(&contents).expect("Failed to parse roles file")
+}
+
+// Rough approximation: ~4 characters per token, plus a per-message overhead
+// for the role/formatting wrapper that the API adds around each message.
+fn estimate_tokens(message: &Message) -> u64 {
+    (message.content.len() as u64) / 4 + 4
+}
+
+// Known context windows for common models. Unrecognized models fall back to
+// the conservative gpt-4 limit.
+fn model_context_limit(model: &str) -> u64 {
+    match model {
+        "gpt-4" => 8192,
+        "gpt-4-32k" => 32768,
+        "gpt-4o" => 128000,
+        "gpt-4-turbo" => 128000,
+        "gpt-3.5-turbo" => 4096,
+        "gpt-3.5-turbo-16k" => 16384,
+        _ => 8192,
+    }
+}
+
+// Drops the oldest non-system messages, one at a time, until the estimated
+// token usage fits within `limit`. messages[0] (the system prompt) is never
+// removed.
+fn trim_to_context_window(messages: &mut Vec<Message>, limit: u64) {
+    let reply_priming_tokens = 3;
+
+    let total = |messages: &Vec<Message>| -> u64 {
+        messages.iter().map(estimate_tokens).sum::<u64>() + reply_priming_tokens
+    };
+
+    while total(messages) > limit && messages.len() > 1 {
+        messages.remove(1);
+    }
+}
+
 impl GptClient {
     pub fn setup_config(dir: &PathBuf) {
         if let Err(e) = ensure_config_file(dir) {
@@ -153,6 +304,12 @@ impl GptClient {
                 config_path.to_str().unwrap(),
                 FileFormat::Toml,
             ))
+            // e.g. CGIP_API_KEY or CGIP_PROXY override whatever is on disk,
+            // so secrets and per-shell overrides never need to touch config.toml.
+            // No separator: AppConfig is flat, and several field names (api_key,
+            // max_context_tokens, ...) contain underscores that a separator would
+            // otherwise split into nested keys, silently breaking the override.
+            .add_source(Environment::with_prefix("CGIP"))
             .build()
             .unwrap();
         let loaded_config = config
@@ -183,6 +340,22 @@ impl GptClient {
                 config.show_context = value.parse().expect("Invalid value for show_context")
             }
             "markdown" => config.markdown = value.parse().expect("Invalid value for markdown"),
+            "stream" => config.stream = value.parse().expect("Invalid value for stream"),
+            "temperature" => {
+                config.temperature = Some(value.parse().expect("Invalid value for temperature"))
+            }
+            "top_p" => config.top_p = Some(value.parse().expect("Invalid value for top_p")),
+            "max_tokens" => {
+                config.max_tokens = Some(value.parse().expect("Invalid value for max_tokens"))
+            }
+            "api_base" => config.api_base = value.to_string(),
+            "api_key" => config.api_key = Some(value.to_string()),
+            "max_context_tokens" => {
+                config.max_context_tokens =
+                    Some(value.parse().expect("Invalid value for max_context_tokens"))
+            }
+            "proxy" => config.proxy = Some(value.to_string()),
+            "dry_run" => config.dry_run = value.parse().expect("Invalid value for dry_run"),
             _ => eprintln!("Invalid configuration key"),
         }
 
@@ -198,17 +371,41 @@ impl GptClient {
             "show_progress" => self.config.show_progress.to_string(),
             "show_context" => self.config.show_context.to_string(),
             "markdown" => self.config.markdown.to_string(),
+            "stream" => self.config.stream.to_string(),
+            "temperature" => self
+                .config
+                .temperature
+                .map_or(String::new(), |v| v.to_string()),
+            "top_p" => self.config.top_p.map_or(String::new(), |v| v.to_string()),
+            "max_tokens" => self
+                .config
+                .max_tokens
+                .map_or(String::new(), |v| v.to_string()),
+            "api_base" => self.config.api_base.clone(),
+            "api_key" => self.config.api_key.clone().unwrap_or_default(),
+            "max_context_tokens" => self
+                .config
+                .max_context_tokens
+                .map_or(String::new(), |v| v.to_string()),
+            "proxy" => self.config.proxy.clone().unwrap_or_default(),
+            "dry_run" => self.config.dry_run.to_string(),
             _ => "Invalid configuration key".to_string(),
         }
     }
 
     pub fn new() -> Self {
-        let config_directory = config_dir()
-            .expect("Failed to find config directory")
-            .join("cgip");
+        // CGIP_CONFIG_DIR relocates the whole config directory (config.toml,
+        // roles.yaml), overriding the OS-default config_dir()/cgip.
+        let config_directory = match env::var("CGIP_CONFIG_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => config_dir()
+                .expect("Failed to find config directory")
+                .join("cgip"),
+        };
 
         Self::setup_config(&config_directory);
         let config = Self::load_config(&config_directory);
+        let roles = load_roles(&config_directory);
 
         let os = env::consts::OS;
 
@@ -230,9 +427,25 @@ impl GptClient {
                 role: Role::System.to_string().to_lowercase(),
                 content: system_prompt.trim().to_string(),
             }],
+            roles,
         }
     }
 
+    // Replaces the default system prompt (messages[0]) with the named role's
+    // prompt. Panics with a clear message if the role isn't defined.
+    pub fn apply_role(&mut self, name: &str) {
+        let role = self
+            .roles
+            .iter()
+            .find(|role| role.name == name)
+            .unwrap_or_else(|| panic!("No role named '{}' found in roles.yaml", name));
+
+        self.messages[0] = Message {
+            role: Role::System.to_string().to_lowercase(),
+            content: role.prompt.clone(),
+        };
+    }
+
     pub fn add_message(&mut self, role: Role, text: String) -> &mut Self {
         self.messages.push(Message {
             role: role.to_string(),
@@ -257,12 +470,32 @@ impl GptClient {
 
     //complete method, generates response text in cli.rs within run
     pub fn complete(&mut self) -> String {
-        // Retrieve the API key from the environment variable
-        let api_key =
-            env::var("OPENAI_API_KEY").expect("Missing OPENAI_API_KEY environment variable");
-
-        let client = reqwest::blocking::Client::new();
-        let url = "https://api.openai.com/v1/chat/completions";
+        // Prefer the configured API key (for non-OpenAI backends), falling back to the
+        // environment variable. An empty key is allowed for local servers that don't
+        // require auth.
+        let api_key = self
+            .config
+            .api_key
+            .clone()
+            .or_else(|| env::var("OPENAI_API_KEY").ok())
+            .unwrap_or_default();
+
+        let context_limit = self
+            .config
+            .max_context_tokens
+            .unwrap_or_else(|| model_context_limit(&self.config.model));
+        trim_to_context_window(&mut self.messages, context_limit);
+
+        let mut client_builder = reqwest::blocking::Client::builder();
+        if let Some(proxy_url) = &self.config.proxy {
+            let proxy = reqwest::Proxy::all(proxy_url)
+                .unwrap_or_else(|e| panic!("Invalid proxy URL: {}", e));
+            client_builder = client_builder.proxy(proxy);
+        }
+        let client = client_builder
+            .build()
+            .expect("Failed to build HTTP client");
+        let url = format!("{}/chat/completions", self.config.api_base);
 
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -270,15 +503,22 @@ impl GptClient {
             header::HeaderValue::from_static("application/json"),
         );
 
-        let auth_header = match header::HeaderValue::from_str(&format!("Bearer {}", api_key)) {
-            Ok(header) => header,
-            Err(e) => panic!("Error while assigning auth header: {}", e),
-        };
-        headers.insert(header::AUTHORIZATION, auth_header);
+        if !api_key.is_empty() {
+            let auth_header = match header::HeaderValue::from_str(&format!("Bearer {}", api_key))
+            {
+                Ok(header) => header,
+                Err(e) => panic!("Error while assigning auth header: {}", e),
+            };
+            headers.insert(header::AUTHORIZATION, auth_header);
+        }
 
         let chat_request = ChatRequest {
             model: self.config.model.clone(),
             messages: self.messages.clone(),
+            stream: if self.config.stream { Some(true) } else { None },
+            temperature: self.config.temperature,
+            top_p: self.config.top_p,
+            max_tokens: self.config.max_tokens,
         };
 
         let request_body = match serde_json::to_string(&chat_request) {
@@ -286,19 +526,31 @@ impl GptClient {
             Err(e) => panic!("Error while serializing request body: {}", e),
         };
 
+        if self.config.dry_run {
+            println!("POST {}", url);
+            println!("{}", request_body);
+            return String::new();
+        }
+
         let response = client
-            .post(url)
+            .post(&url)
             .headers(headers)
             .body(request_body)
             .timeout(std::time::Duration::from_secs(60))
             .send();
 
         let response = match response {
-            Ok(response) => response.text(),
+            Ok(response) => response,
             Err(e) => panic!("Error in response: {}", e),
         };
 
-        let response_text = match response {
+        if self.config.stream {
+            let result = read_streaming_response(response);
+            self.add_message(Role::Assistant, result.clone());
+            return result;
+        }
+
+        let response_text = match response.text() {
             Ok(response) => response,
             Err(e) => panic!("Error in response text: {}", e),
         };
@@ -340,6 +592,15 @@ mod tests {
             show_progress: true,
             show_context: false,
             markdown: false,
+            stream: false,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            api_base: "https://api.openai.com/v1".to_string(),
+            api_key: None,
+            max_context_tokens: None,
+            proxy: None,
+            dry_run: false,
         };
         let config_path = config_dir_path.join("config.toml");
         let contents = toml::to_string(&custom_config).expect("Failed to serialize custom config");
@@ -421,6 +682,15 @@ mod tests {
             show_progress: true,
             show_context: true,
             markdown: true,
+            stream: false,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+            api_base: "https://api.openai.com/v1".to_string(),
+            api_key: None,
+            max_context_tokens: None,
+            proxy: None,
+            dry_run: false,
         };
         let custom_contents = toml::to_string(&custom_config).unwrap();
         File::create(&config_path)
@@ -438,4 +708,55 @@ mod tests {
             "The existing custom config should not be overwritten"
         );
     }
+
+    #[test]
+    fn test_trim_to_context_window_drops_oldest_non_system_first() {
+        let mut messages = vec![
+            Message {
+                role: "system".to_string(),
+                content: "s".repeat(40),
+            },
+            Message {
+                role: "user".to_string(),
+                content: "oldest".repeat(10),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: "newest".repeat(10),
+            },
+        ];
+
+        trim_to_context_window(&mut messages, 40);
+
+        assert_eq!(
+            messages.len(),
+            2,
+            "oldest non-system message should be dropped once the limit is exceeded"
+        );
+        assert_eq!(
+            messages[0].role, "system",
+            "system prompt must stay at index 0"
+        );
+        assert_eq!(
+            messages[1].content,
+            "newest".repeat(10),
+            "the most recent message should be kept over the older one"
+        );
+    }
+
+    #[test]
+    fn test_trim_to_context_window_never_removes_system_prompt() {
+        let mut messages = vec![Message {
+            role: "system".to_string(),
+            content: "s".repeat(1000),
+        }];
+
+        trim_to_context_window(&mut messages, 1);
+
+        assert_eq!(
+            messages.len(),
+            1,
+            "the system prompt must survive even when it alone exceeds the limit"
+        );
+    }
 }
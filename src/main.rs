@@ -18,6 +18,15 @@ fn main() {
     let args = Args::parse();
 
     let mut client = GptClient::new();
+
+    if let Some(role) = args.role.clone() {
+        client.apply_role(&role);
+    }
+
+    if args.dry_run {
+        client.config.dry_run = true;
+    }
+
     if let Some(SubCommands::Config(config_sc)) = &args.subcmd {
         sub::config::run(&mut client, config_sc);
         return;